@@ -1,11 +1,156 @@
 use serde::{Deserialize, Serialize};
-use std::env;
+use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
+use std::net::{AddrParseError, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use zeroize::{Zeroize, Zeroizing};
 use rand::Rng;
 use base64::{Engine as _, engine};
+use clap::{Parser, Subcommand};
+use ipnetwork::{IpNetworkError, Ipv4Network};
+use qrcode::render::unicode;
 use qrcode::QrCode;
 use image::Luma;
+use thiserror::Error;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+const OUTPUT_DIR: &str = "wg-clients";
+
+#[derive(Error, Debug)]
+enum Error {
+    #[error("failed to {action} {path}: {source}")]
+    Io { action: &'static str, path: String, source: std::io::Error },
+    #[error("failed to parse config file: {0}")]
+    ParseConfig(#[from] serde_json::Error),
+    #[error("failed to generate QR code: {0}")]
+    QrCode(#[from] qrcode::types::QrError),
+    #[error("failed to encode QR code image: {0}")]
+    QrImage(#[from] image::ImageError),
+    #[error("client {0} is missing a private key")]
+    MissingPrivateKey(String),
+    #[error("{0} has a malformed private key, expected 32 base64-encoded bytes")]
+    InvalidPrivateKey(String),
+    #[error("client {0} is missing a public key")]
+    MissingPublicKey(String),
+    #[error("server is missing a private key")]
+    MissingServerPrivateKey,
+    #[error("invalid subnet {subnet}: {source}")]
+    InvalidSubnet { subnet: String, source: IpNetworkError },
+    #[error("subnet {0} has no usable host addresses")]
+    SubnetEmpty(String),
+    #[error("invalid address {address} for client {name}: {source}")]
+    InvalidAddress { address: String, name: String, source: AddrParseError },
+    #[error("address {0} is assigned to more than one client")]
+    DuplicateAddress(Ipv4Addr),
+    #[error("subnet {subnet} is full, no addresses left for client {name}")]
+    SubnetFull { subnet: String, name: String },
+    #[error("client {0} already exists")]
+    ClientExists(String),
+    #[error("client {0} not found")]
+    ClientNotFound(String),
+    #[error("`{0}` was not found on PATH; is the wireguard-tools package installed?")]
+    CommandNotFound(String),
+    #[error("failed to run `{command}`: {source}")]
+    CommandFailed { command: String, source: std::io::Error },
+    #[error("`{command}` exited with {status}: {stderr}")]
+    CommandExitedNonZero { command: String, status: ExitStatus, stderr: String },
+    #[error("{0} client(s) failed to generate, see above")]
+    ClientFailures(usize),
+}
+
+/// Wraps a WireGuard private key so it is scrubbed from memory on drop
+/// instead of lingering in a plain `String` for the program's lifetime.
+/// Serializes/deserializes transparently as the underlying string.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(transparent)]
+struct SecretString(String);
+
+impl std::fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretString(\"[redacted]\")")
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl std::ops::Deref for SecretString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        SecretString(value)
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Generate and manage WireGuard client configs")]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Regenerate all client and server configs from a config file
+    Generate {
+        /// Path to the config JSON file
+        config: PathBuf,
+        /// Also render each client's QR code to the terminal
+        #[arg(long)]
+        stdout_qr: bool,
+    },
+    /// Add a new client, generating its key and address
+    Add {
+        /// Path to the config JSON file
+        config: PathBuf,
+        /// Name of the new client
+        #[arg(long)]
+        name: String,
+        /// Address to assign the client; auto-allocated from the subnet if omitted
+        #[arg(long)]
+        address: Option<String>,
+        /// Also render the new client's QR code to the terminal
+        #[arg(long)]
+        stdout_qr: bool,
+    },
+    /// Remove a client and its output files
+    Remove {
+        /// Path to the config JSON file
+        config: PathBuf,
+        /// Name of the client to remove
+        #[arg(long)]
+        name: String,
+    },
+    /// List every client with its address and public key
+    List {
+        /// Path to the config JSON file
+        config: PathBuf,
+    },
+    /// Push the generated peers onto a live WireGuard interface
+    Apply {
+        /// Path to the config JSON file
+        config: PathBuf,
+        /// Name of the kernel WireGuard interface, e.g. wg0
+        #[arg(long)]
+        iface: String,
+        /// Run `wg-quick up <iface>` before applying peers
+        #[arg(long)]
+        up: bool,
+        /// Run `wg-quick down <iface>` instead of applying peers
+        #[arg(long)]
+        down: bool,
+    },
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 struct ServerConfig {
@@ -14,13 +159,17 @@ struct ServerConfig {
     dns: String,
     subnet: String,
     public_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    private_key: Option<SecretString>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct ClientConfig {
     name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    private_key: Option<String>,
+    private_key: Option<SecretString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    public_key: Option<String>,
     address: String,
 }
 
@@ -30,21 +179,52 @@ struct Config {
     client: Vec<ClientConfig>,
 }
 
-/// Generates a WireGuard private key (32 random bytes encoded in Base64)
-fn generate_private_key() -> String {
+/// Generates a WireGuard keypair: 32 random bytes are clamped into a valid
+/// Curve25519 scalar (mirroring `wg genkey`), then the matching public key is
+/// derived via scalar multiplication with the Curve25519 base point (`wg pubkey`).
+/// Returns the pair as `(private_key, public_key)`, both Base64-encoded.
+fn generate_keypair() -> (SecretString, String) {
     let mut rng = rand::rng();
-    let mut key = [0u8; 32];
-    rng.fill(&mut key);
-    engine::general_purpose::STANDARD.encode(key) // Use STANDARD.encode
+    let mut key = Zeroizing::new([0u8; 32]);
+    rng.fill(&mut *key);
+
+    // Clamp to a valid Curve25519 scalar.
+    key[0] &= 248;
+    key[31] &= 127;
+    key[31] |= 64;
+
+    let private_key = SecretString::from(engine::general_purpose::STANDARD.encode(*key));
+
+    let secret = StaticSecret::from(*key);
+    let public = PublicKey::from(&secret);
+    let public_key = engine::general_purpose::STANDARD.encode(public.as_bytes());
+
+    (private_key, public_key)
 }
 
-fn generate_client_config(client: &ClientConfig, server: &ServerConfig) -> String {
-    let address = server.subnet.replace("{address}", &client.address);
-    let private_key = client.private_key.as_deref().unwrap_or_else(|| {
-        panic!("Private key not provided for client {}", client.name);
-    });
+/// Derives the matching public key for an existing Base64-encoded private
+/// key, for clients that bring their own key instead of a generated one.
+/// Returns `None` if the private key isn't valid Base64 for 32 bytes.
+fn derive_public_key(private_key: &str) -> Option<String> {
+    let decoded = engine::general_purpose::STANDARD.decode(private_key).ok()?;
+    let key: [u8; 32] = decoded.try_into().ok()?;
+    let secret = StaticSecret::from(key);
+    let public = PublicKey::from(&secret);
+    Some(engine::general_purpose::STANDARD.encode(public.as_bytes()))
+}
+
+fn generate_client_config(client: &ClientConfig, server: &ServerConfig) -> Result<String, Error> {
+    let network: Ipv4Network = server
+        .subnet
+        .parse()
+        .map_err(|source| Error::InvalidSubnet { subnet: server.subnet.clone(), source })?;
+    let address = format!("{}/{}", client.address, network.prefix());
+    let private_key = client
+        .private_key
+        .as_deref()
+        .ok_or_else(|| Error::MissingPrivateKey(client.name.clone()))?;
 
-    format!(
+    Ok(format!(
         "[Interface]\n\
         PrivateKey = {}\n\
         Address = {}\n\
@@ -60,87 +240,493 @@ fn generate_client_config(client: &ClientConfig, server: &ServerConfig) -> Strin
         server.public_key,
         server.host,
         server.port
-    )
+    ))
 }
 
-fn generate_qr_code_png(config_content: &str, output_path: &str) {
-    let code = QrCode::new(config_content.as_bytes()).unwrap();
+fn generate_qr_code_png(config_content: &str, output_path: &str) -> Result<(), Error> {
+    let code = QrCode::new(config_content.as_bytes())?;
     let image = code.render::<Luma<u8>>().build();
-    image.save(output_path).unwrap();
+    image.save(output_path)?;
+    Ok(())
 }
 
-fn main() {
-    // Read command-line arguments
-    let args: Vec<String> = env::args().collect();
+/// Renders a config as a scannable QR code using half-block Unicode, for
+/// admins who want to scan a client straight from an SSH session.
+fn render_qr_terminal(config_content: &str) -> Result<String, Error> {
+    let code = QrCode::new(config_content.as_bytes())?;
+    Ok(code.render::<unicode::Dense1x2>().build())
+}
 
-    // Check if the config file path is provided
-    if args.len() < 2 {
-        eprintln!("Usage: {} <path_to_config.json>", args[0]);
-        return;
-    }
+/// Returns the first usable host address of the subnet, i.e. the network
+/// address plus one (e.g. `10.55.55.0/24` -> `10.55.55.1`).
+fn first_subnet_host(network: &Ipv4Network) -> Option<Ipv4Addr> {
+    let host = Ipv4Addr::from(u32::from(network.network()).checked_add(1)?);
+    (network.contains(host) && host != network.broadcast()).then_some(host)
+}
+
+/// Assigns the next free host address in `server.subnet` to every client
+/// whose `address` is empty, reserving the subnet's first usable host for the
+/// server itself, skipping addresses already claimed by other clients, and
+/// never handing out the subnet's network or broadcast address.
+/// Errors if the subnet is malformed, a client's existing address collides
+/// with another, or the subnet runs out of free host addresses.
+fn allocate_addresses(server: &ServerConfig, clients: &mut [ClientConfig]) -> Result<(), Error> {
+    let network: Ipv4Network = server
+        .subnet
+        .parse()
+        .map_err(|source| Error::InvalidSubnet { subnet: server.subnet.clone(), source })?;
 
-    let config_path = &args[1];
+    let server_address = first_subnet_host(&network).ok_or_else(|| Error::SubnetEmpty(server.subnet.clone()))?;
 
-    // Read the JSON file
-    let config_data = match fs::read_to_string(config_path) {
-        Ok(data) => data,
-        Err(err) => {
-            eprintln!("Failed to read config file: {}", err);
-            return;
+    let mut used: HashSet<Ipv4Addr> = HashSet::new();
+    used.insert(server_address);
+
+    for client in clients.iter() {
+        if client.address.is_empty() {
+            continue;
         }
-    };
+        let address: Ipv4Addr = client.address.parse().map_err(|source| Error::InvalidAddress {
+            address: client.address.clone(),
+            name: client.name.clone(),
+            source,
+        })?;
+        if !used.insert(address) {
+            return Err(Error::DuplicateAddress(address));
+        }
+    }
 
-    // Parse the JSON data into the Config struct
-    let mut config: Config = match serde_json::from_str(&config_data) {
-        Ok(config) => config,
-        Err(err) => {
-            eprintln!("Failed to parse config file: {}", err);
-            return;
+    let broadcast = network.broadcast();
+    let mut candidates = network.iter().skip(1).filter(|candidate| *candidate != broadcast);
+    for client in clients.iter_mut() {
+        if !client.address.is_empty() {
+            continue;
         }
-    };
+        let address = candidates
+            .by_ref()
+            .find(|candidate| !used.contains(candidate))
+            .ok_or_else(|| Error::SubnetFull { subnet: server.subnet.clone(), name: client.name.clone() })?;
+        used.insert(address);
+        client.address = address.to_string();
+        println!("Assigned address {} to client {}", address, client.name);
+    }
+
+    Ok(())
+}
+
+/// Builds the server-side `[Interface]` section plus one `[Peer]` block per
+/// client, so the resulting file can be dropped straight onto the WireGuard
+/// host as its interface config.
+fn generate_server_config(server: &ServerConfig, clients: &[ClientConfig]) -> Result<String, Error> {
+    let private_key = server.private_key.as_deref().ok_or(Error::MissingServerPrivateKey)?;
+    let network: Ipv4Network = server
+        .subnet
+        .parse()
+        .map_err(|source| Error::InvalidSubnet { subnet: server.subnet.clone(), source })?;
+    let address = first_subnet_host(&network).ok_or_else(|| Error::SubnetEmpty(server.subnet.clone()))?;
+
+    let mut content = format!(
+        "[Interface]\n\
+        PrivateKey = {}\n\
+        Address = {}/{}\n\
+        ListenPort = {}\n",
+        private_key, address, network.prefix(), server.port
+    );
+
+    for client in clients {
+        let public_key = client
+            .public_key
+            .as_deref()
+            .ok_or_else(|| Error::MissingPublicKey(client.name.clone()))?;
+        content.push_str(&format!(
+            "\n[Peer]\n\
+            PublicKey = {}\n\
+            AllowedIPs = {}/32\n",
+            public_key, client.address
+        ));
+    }
+
+    Ok(content)
+}
+
+fn load_config(config_path: &Path) -> Result<Config, Error> {
+    let config_data = fs::read_to_string(config_path).map_err(|source| Error::Io {
+        action: "read config file",
+        path: config_path.display().to_string(),
+        source,
+    })?;
+    Ok(serde_json::from_str(&config_data)?)
+}
+
+/// Restricts a freshly written file containing key material to owner-only
+/// access (mode `0600`) on Unix; a no-op on platforms without Unix permission bits.
+#[cfg(unix)]
+fn restrict_permissions(path: &str) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut perms = fs::metadata(path)
+        .map_err(|source| Error::Io { action: "stat", path: path.to_string(), source })?
+        .permissions();
+    perms.set_mode(0o600);
+    fs::set_permissions(path, perms).map_err(|source| Error::Io { action: "set permissions on", path: path.to_string(), source })
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &str) -> Result<(), Error> {
+    Ok(())
+}
+
+fn save_config(config: &Config) -> Result<(), Error> {
+    let updated_config_path = format!("{}/updated_config.json", OUTPUT_DIR);
+    let content = serde_json::to_string_pretty(config)?;
+    fs::write(&updated_config_path, content).map_err(|source| Error::Io {
+        action: "write",
+        path: updated_config_path.clone(),
+        source,
+    })?;
+    restrict_permissions(&updated_config_path)?;
+    println!("Saved updated config file at {}", updated_config_path);
+    Ok(())
+}
+
+fn ensure_output_dir() -> Result<(), Error> {
+    if !Path::new(OUTPUT_DIR).exists() {
+        fs::create_dir(OUTPUT_DIR).map_err(|source| Error::Io {
+            action: "create directory",
+            path: OUTPUT_DIR.to_string(),
+            source,
+        })?;
+    }
+    Ok(())
+}
+
+/// Writes a client's `.conf` file and PNG QR code under `OUTPUT_DIR`, and
+/// additionally renders the QR code to the terminal if `stdout_qr` is set.
+fn write_client_files(client: &ClientConfig, server: &ServerConfig, stdout_qr: bool) -> Result<(), Error> {
+    let config_content = generate_client_config(client, server)?;
+    let config_filename = format!("{}/{}.conf", OUTPUT_DIR, client.name);
+    fs::write(&config_filename, &config_content).map_err(|source| Error::Io {
+        action: "write",
+        path: config_filename.clone(),
+        source,
+    })?;
+    restrict_permissions(&config_filename)?;
+    println!("Generated configuration for {} at {}", client.name, config_filename);
+
+    let qr_code_filename = format!("{}/{}_qr.png", OUTPUT_DIR, client.name);
+    generate_qr_code_png(&config_content, &qr_code_filename)?;
+    println!("Generated QR code for {} at {}", client.name, qr_code_filename);
+
+    if stdout_qr {
+        println!("{}", render_qr_terminal(&config_content)?);
+    }
+    Ok(())
+}
+
+/// Writes `OUTPUT_DIR/server.conf` from the current client list.
+fn write_server_config(config: &Config) -> Result<(), Error> {
+    let server_config_content = generate_server_config(&config.server, &config.client)?;
+    let server_config_filename = format!("{}/server.conf", OUTPUT_DIR);
+    fs::write(&server_config_filename, &server_config_content).map_err(|source| Error::Io {
+        action: "write",
+        path: server_config_filename.clone(),
+        source,
+    })?;
+    restrict_permissions(&server_config_filename)?;
+    println!("Generated server configuration at {}", server_config_filename);
+    Ok(())
+}
+
+/// Writes `server.conf` if we hold the server's private key, and otherwise
+/// leaves it alone: most configs only record the real server's `public_key`
+/// and manage `server.conf` out of band, so having no private key on file is
+/// the common case, not an error that should abort a `generate`/`add`/`remove` run.
+fn write_server_config_if_possible(config: &Config) -> Result<(), Error> {
+    if config.server.private_key.is_none() {
+        println!("Skipping server.conf: no server private key on file");
+        return Ok(());
+    }
+    write_server_config(config)
+}
+
+/// Generates a fresh server keypair only when the server has neither a
+/// `private_key` nor a pre-existing `public_key`. A config that already
+/// supplies `public_key` (the real server's key) is left untouched, even
+/// without a matching `private_key` on file, so we never hand out a key the
+/// real server doesn't hold. If a `private_key` is on file but `public_key`
+/// hasn't been filled in yet, it's derived, mirroring `ensure_client_keypair`.
+fn ensure_server_keypair(server: &mut ServerConfig) -> Result<(), Error> {
+    if server.private_key.is_none() && server.public_key.is_empty() {
+        let (private_key, public_key) = generate_keypair();
+        server.private_key = Some(private_key);
+        server.public_key = public_key;
+        println!("Generated keypair for server");
+    } else if server.public_key.is_empty() {
+        server.public_key = derive_public_key(server.private_key.as_deref().unwrap())
+            .ok_or_else(|| Error::InvalidPrivateKey("server".to_string()))?;
+        println!("Derived public key for server");
+    }
+    Ok(())
+}
+
+/// Ensures a client has both a `private_key` and a matching `public_key`:
+/// generates a fresh keypair if `private_key` is unset (replacing any
+/// `public_key` already there), or derives the public key
+/// from an operator-supplied `private_key` if only that was provided.
+fn ensure_client_keypair(client: &mut ClientConfig) -> Result<(), Error> {
+    if client.private_key.is_none() {
+        let (private_key, public_key) = generate_keypair();
+        client.private_key = Some(private_key);
+        client.public_key = Some(public_key);
+        println!("Generated keypair for client {}", client.name);
+    } else if client.public_key.is_none() {
+        let public_key = derive_public_key(client.private_key.as_deref().unwrap())
+            .ok_or_else(|| Error::InvalidPrivateKey(format!("client {}", client.name)))?;
+        client.public_key = Some(public_key);
+        println!("Derived public key for client {}", client.name);
+    }
+    Ok(())
+}
 
-    // Directory to save all output files
-    let output_dir = "wg-clients";
+fn generate_keys_and_addresses(config: &mut Config) -> Result<(), Error> {
+    ensure_server_keypair(&mut config.server)?;
+
+    for client in &mut config.client {
+        ensure_client_keypair(client)?;
+    }
+
+    allocate_addresses(&config.server, &mut config.client)
+}
 
-    // Create the output directory if it doesn't exist
-    if !Path::new(output_dir).exists() {
-        if let Err(err) = fs::create_dir(output_dir) {
-            eprintln!("Failed to create output directory: {}", err);
-            return;
+fn cmd_generate(config_path: &Path, stdout_qr: bool) -> Result<(), Error> {
+    let mut config = load_config(config_path)?;
+    ensure_output_dir()?;
+    generate_keys_and_addresses(&mut config)?;
+
+    let mut failures = 0;
+    for client in &config.client {
+        if let Err(err) = write_client_files(client, &config.server, stdout_qr) {
+            eprintln!("{}", err);
+            failures += 1;
         }
     }
 
-    // Generate private keys for clients if not provided
+    save_config(&config)?;
+    write_server_config_if_possible(&config)?;
+
+    if failures > 0 {
+        return Err(Error::ClientFailures(failures));
+    }
+    Ok(())
+}
+
+fn cmd_add(config_path: &Path, name: String, address: Option<String>, stdout_qr: bool) -> Result<(), Error> {
+    let mut config = load_config(config_path)?;
+
+    if config.client.iter().any(|client| client.name == name) {
+        return Err(Error::ClientExists(name));
+    }
+
+    ensure_output_dir()?;
+
+    ensure_server_keypair(&mut config.server)?;
+
+    config.client.push(ClientConfig {
+        name: name.clone(),
+        private_key: None,
+        public_key: None,
+        address: address.unwrap_or_default(),
+    });
+
+    // Covers pre-existing clients too, not just the one just pushed: a config
+    // that was hand-edited or never run through `generate` may have clients
+    // with no keys yet, and server.conf needs every client's public key. A
+    // malformed key on an unrelated existing client shouldn't stop us from
+    // adding the new one, so report and move on instead of aborting.
     for client in &mut config.client {
-        if client.private_key.is_none() {
-            client.private_key = Some(generate_private_key());
-            println!("Generated private key for client {}", client.name);
+        if let Err(err) = ensure_client_keypair(client) {
+            eprintln!("{}", err);
         }
     }
 
-    // Generate a configuration file and QR code for each client
-    for client in &config.client {
-        let config_content = generate_client_config(client, &config.server);
-        let config_filename = format!("{}/{}.conf", output_dir, client.name);
+    allocate_addresses(&config.server, &mut config.client)?;
+
+    let client = config.client.iter().find(|client| client.name == name).unwrap();
+    write_client_files(client, &config.server, stdout_qr)?;
+    save_config(&config)?;
+    write_server_config_if_possible(&config)
+}
+
+fn cmd_remove(config_path: &Path, name: String) -> Result<(), Error> {
+    let mut config = load_config(config_path)?;
+
+    let index = config
+        .client
+        .iter()
+        .position(|client| client.name == name)
+        .ok_or_else(|| Error::ClientNotFound(name.clone()))?;
+    config.client.remove(index);
 
-        // Save the configuration file
-        if let Err(err) = fs::write(&config_filename, &config_content) {
-            eprintln!("Failed to write configuration for {}: {}", client.name, err);
+    let output_files = [
+        format!("{}/{}.conf", OUTPUT_DIR, name),
+        format!("{}/{}_qr.png", OUTPUT_DIR, name),
+    ];
+    for path in output_files {
+        match fs::remove_file(&path) {
+            Ok(()) => println!("Removed {}", path),
+            Err(source) if source.kind() == std::io::ErrorKind::NotFound => {}
+            Err(source) => eprintln!("{}", Error::Io { action: "remove", path, source }),
+        }
+    }
+
+    save_config(&config)?;
+    write_server_config_if_possible(&config)
+}
+
+/// Runs an external command, capturing stdout/stderr and surfacing a missing
+/// binary or a non-zero exit code as an error instead of panicking.
+fn run_command(program: &str, args: &[&str]) -> Result<(), Error> {
+    let command = format!("{} {}", program, args.join(" "));
+    let output = Command::new(program).args(args).output().map_err(|source| {
+        if source.kind() == std::io::ErrorKind::NotFound {
+            Error::CommandNotFound(program.to_string())
         } else {
-            println!("Generated configuration for {} at {}", client.name, config_filename);
+            Error::CommandFailed { command: command.clone(), source }
         }
+    })?;
+
+    if !output.status.success() {
+        return Err(Error::CommandExitedNonZero {
+            command,
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Pushes every client's public key and allocated address onto `iface` as a
+/// `wg` peer, mirroring what a WireGuard management daemon does on reload.
+fn apply_peers(config: &Config, iface: &str) -> Result<(), Error> {
+    for client in &config.client {
+        let public_key = client
+            .public_key
+            .as_deref()
+            .ok_or_else(|| Error::MissingPublicKey(client.name.clone()))?;
+        let allowed_ips = format!("{}/32", client.address);
+        run_command("wg", &["set", iface, "peer", public_key, "allowed-ips", &allowed_ips])?;
+        println!("Applied peer {} to {}", client.name, iface);
+    }
+    Ok(())
+}
+
+fn cmd_apply(config_path: &Path, iface: String, up: bool, down: bool) -> Result<(), Error> {
+    let config = load_config(config_path)?;
+
+    if down {
+        run_command("wg-quick", &["down", &iface])?;
+        println!("Brought down interface {}", iface);
+        return Ok(());
+    }
+
+    if up {
+        run_command("wg-quick", &["up", &iface])?;
+        println!("Brought up interface {}", iface);
+    }
+
+    apply_peers(&config, &iface)
+}
+
+fn cmd_list(config_path: &Path) -> Result<(), Error> {
+    let config = load_config(config_path)?;
+
+    for client in &config.client {
+        let address = if client.address.is_empty() { "unassigned" } else { &client.address };
+        let public_key = client.public_key.as_deref().unwrap_or("not generated");
+        println!("{}\t{}\t{}", client.name, address, public_key);
+    }
+    Ok(())
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Commands::Generate { config, stdout_qr } => cmd_generate(&config, stdout_qr),
+        Commands::Add { config, name, address, stdout_qr } => cmd_add(&config, name, address, stdout_qr),
+        Commands::Remove { config, name } => cmd_remove(&config, name),
+        Commands::List { config } => cmd_list(&config),
+        Commands::Apply { config, iface, up, down } => cmd_apply(&config, iface, up, down),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derive_public_key_matches_generated_keypair() {
+        let (private_key, public_key) = generate_keypair();
+        let derived = derive_public_key(&private_key).expect("valid private key");
+        assert_eq!(derived, public_key);
+    }
+
+    #[test]
+    fn derive_public_key_rejects_malformed_input() {
+        assert!(derive_public_key("not valid base64!!").is_none());
+        let too_short = engine::general_purpose::STANDARD.encode([0u8; 16]);
+        assert!(derive_public_key(&too_short).is_none());
+    }
+
+    fn test_server(subnet: &str) -> ServerConfig {
+        ServerConfig {
+            host: "vpn.example.com".to_string(),
+            port: "51820".to_string(),
+            dns: "1.1.1.1".to_string(),
+            subnet: subnet.to_string(),
+            public_key: "serverpublickey".to_string(),
+            private_key: None,
+        }
+    }
+
+    fn test_client(name: &str, address: &str) -> ClientConfig {
+        ClientConfig {
+            name: name.to_string(),
+            private_key: None,
+            public_key: None,
+            address: address.to_string(),
+        }
+    }
+
+    #[test]
+    fn allocate_addresses_skips_server_address_and_assigns_next_free_host() {
+        let server = test_server("10.55.55.0/29");
+        let mut clients = vec![test_client("a", "")];
+        allocate_addresses(&server, &mut clients).expect("allocation succeeds");
+        assert_eq!(clients[0].address, "10.55.55.2");
+    }
 
-        // Generate and save the QR code as a PNG image
-        let qr_code_filename = format!("{}/{}_qr.png", output_dir, client.name);
-        generate_qr_code_png(&config_content, &qr_code_filename);
-        println!("Generated QR code for {} at {}", client.name, qr_code_filename);
+    #[test]
+    fn allocate_addresses_rejects_duplicate_existing_addresses() {
+        let server = test_server("10.55.55.0/24");
+        let mut clients = vec![test_client("a", "10.55.55.5"), test_client("b", "10.55.55.5")];
+        let err = allocate_addresses(&server, &mut clients).unwrap_err();
+        assert!(matches!(err, Error::DuplicateAddress(_)));
     }
 
-    // Save the updated JSON with generated private keys
-    let updated_config_path = format!("{}/updated_config.json", output_dir);
-    if let Err(err) = fs::write(&updated_config_path, serde_json::to_string_pretty(&config).unwrap()) {
-        eprintln!("Failed to save updated config file: {}", err);
-    } else {
-        println!("Saved updated config file at {}", updated_config_path);
+    #[test]
+    fn allocate_addresses_never_hands_out_the_broadcast_address() {
+        // A /30 has exactly one usable host left after the server claims the
+        // first: .0 is the network address, .1 the server, .2 the only free
+        // host, .3 the broadcast address that must never be assigned.
+        let server = test_server("10.55.55.0/30");
+        let mut clients = vec![test_client("a", ""), test_client("b", "")];
+        let err = allocate_addresses(&server, &mut clients).unwrap_err();
+        assert!(matches!(err, Error::SubnetFull { .. }));
+        assert_eq!(clients[0].address, "10.55.55.2");
     }
 }